@@ -0,0 +1,230 @@
+//! Persistenter Cache von Datei-Hashes.
+//!
+//! Ein Eintrag ist gültig, solange Größe und Änderungszeitpunkt einer
+//! Datei mit dem zwischengespeicherten Wert übereinstimmen. Ändert sich
+//! eines von beiden, oder wurde mit einem anderen Hash-Algorithmus
+//! gerechnet, wird die Datei beim nächsten Zugriff neu gehasht.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::HashAlgorithm;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    mtime: u64,
+    algorithm: HashAlgorithm,
+    hash: [u8; 32],
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Cache {
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+impl Cache {
+    pub fn load(path: &Path) -> Cache {
+        fs::read(path)
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&mut self, path: &Path) -> std::io::Result<()> {
+        self.prune_stale();
+        let content = serde_json::to_vec(self).unwrap_or_default();
+        fs::write(path, content)
+    }
+
+    // Eine Datei auf Gleichheit von Größe und Änderungszeitpunkt prüfen,
+    // und den Eintrag entfernen, sobald er nicht mehr stimmt. So wächst
+    // der Cache nicht unbegrenzt mit veralteten Einträgen an.
+    pub fn get(
+        &mut self,
+        path: &Path,
+        size: u64,
+        mtime: SystemTime,
+        algorithm: HashAlgorithm,
+    ) -> Option<[u8; 32]> {
+        let mtime = to_epoch_millis(mtime);
+        let valid = self
+            .entries
+            .get(path)
+            .is_some_and(|entry| entry.size == size && entry.mtime == mtime && entry.algorithm == algorithm);
+
+        if valid {
+            self.entries.get(path).map(|entry| entry.hash)
+        } else {
+            self.entries.remove(path);
+            None
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        path: PathBuf,
+        size: u64,
+        mtime: SystemTime,
+        algorithm: HashAlgorithm,
+        hash: [u8; 32],
+    ) {
+        self.entries.insert(
+            path,
+            CacheEntry {
+                size,
+                mtime: to_epoch_millis(mtime),
+                algorithm,
+                hash,
+            },
+        );
+    }
+
+    // Entfernt vor dem Schreiben alle Einträge, deren Datei nicht mehr
+    // existiert oder deren Größe/Änderungszeitpunkt nicht mehr mit dem
+    // zwischengespeicherten Wert übereinstimmt.
+    fn prune_stale(&mut self) {
+        self.entries.retain(|path, entry| {
+            let Ok(metadata) = fs::metadata(path) else {
+                return false;
+            };
+            let Ok(mtime) = metadata.modified() else {
+                return false;
+            };
+            entry.size == metadata.len() && entry.mtime == to_epoch_millis(mtime)
+        });
+    }
+}
+
+// Millisekunden statt Sekunden, damit eine Datei, die innerhalb
+// derselben Sekunde wie ihr zwischengespeicherter Hash geändert wird,
+// nicht fälschlich als unverändert gilt.
+fn to_epoch_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Der Standardpfad der Cache-Datei, falls der Nutzer keinen eigenen angibt.
+pub fn default_cache_path() -> PathBuf {
+    PathBuf::from(".findup-cache.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Write, thread, time::Duration};
+
+    fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "findup-cache-test-{name}-{}-{:?}",
+            std::process::id(),
+            thread::current().id()
+        ));
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(content).unwrap();
+        path
+    }
+
+    fn stat(path: &Path) -> (u64, SystemTime) {
+        let metadata = fs::metadata(path).unwrap();
+        (metadata.len(), metadata.modified().unwrap())
+    }
+
+    #[test]
+    fn hit_on_matching_size_and_mtime() {
+        let path = temp_file("hit", b"hello");
+        let (size, mtime) = stat(&path);
+        let mut cache = Cache::default();
+        cache.insert(path.clone(), size, mtime, HashAlgorithm::Xxh3, [1; 32]);
+
+        assert_eq!(
+            cache.get(&path, size, mtime, HashAlgorithm::Xxh3),
+            Some([1; 32])
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn miss_and_eviction_on_size_change() {
+        let path = temp_file("size", b"hello");
+        let (size, mtime) = stat(&path);
+        let mut cache = Cache::default();
+        cache.insert(path.clone(), size, mtime, HashAlgorithm::Xxh3, [1; 32]);
+
+        assert_eq!(cache.get(&path, size + 1, mtime, HashAlgorithm::Xxh3), None);
+        // Der veraltete Eintrag muss nach dem Fehlschlag entfernt sein.
+        assert_eq!(
+            cache.get(&path, size, mtime, HashAlgorithm::Xxh3),
+            None
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn miss_on_mtime_change() {
+        let path = temp_file("mtime", b"hello");
+        let (size, mtime) = stat(&path);
+        let mut cache = Cache::default();
+        cache.insert(path.clone(), size, mtime, HashAlgorithm::Xxh3, [1; 32]);
+
+        thread::sleep(Duration::from_millis(10));
+        // Gleiche Länge, damit sich nur der Änderungszeitpunkt ändert.
+        fs::write(&path, b"world").unwrap();
+        let (new_size, new_mtime) = stat(&path);
+
+        assert_eq!(
+            cache.get(&path, new_size, new_mtime, HashAlgorithm::Xxh3),
+            None
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn save_prunes_entries_for_deleted_files() {
+        let kept_path = temp_file("kept", b"unchanged");
+        let (kept_size, kept_mtime) = stat(&kept_path);
+        let deleted_path = temp_file("deleted", b"gone soon");
+        let (deleted_size, deleted_mtime) = stat(&deleted_path);
+
+        let mut cache = Cache::default();
+        cache.insert(
+            kept_path.clone(),
+            kept_size,
+            kept_mtime,
+            HashAlgorithm::Xxh3,
+            [2; 32],
+        );
+        cache.insert(
+            deleted_path.clone(),
+            deleted_size,
+            deleted_mtime,
+            HashAlgorithm::Xxh3,
+            [3; 32],
+        );
+        fs::remove_file(&deleted_path).unwrap();
+
+        let cache_path = std::env::temp_dir().join(format!(
+            "findup-cache-test-save-{}-{:?}.json",
+            std::process::id(),
+            thread::current().id()
+        ));
+        cache.save(&cache_path).unwrap();
+
+        let mut reloaded = Cache::load(&cache_path);
+        assert_eq!(
+            reloaded.get(&kept_path, kept_size, kept_mtime, HashAlgorithm::Xxh3),
+            Some([2; 32])
+        );
+        assert!(!reloaded.entries.contains_key(&deleted_path));
+
+        let _ = fs::remove_file(&kept_path);
+        let _ = fs::remove_file(&cache_path);
+    }
+}
@@ -1,13 +1,21 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     error::Error,
     fs::{self, ReadDir},
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
 use clap::{Parser, ValueEnum};
 use colored::Colorize;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use xxhash_rust::xxh3::Xxh3;
+
+mod cache;
+
+use cache::Cache;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -22,9 +30,61 @@ struct Args {
     #[arg(short, long, conflicts_with = "human")]
     machine: bool,
 
-    #[arg(short = 'd', long, default_value_t = 1)]
+    /// Maximale Tiefe, bis zu der rekursiv abgestiegen wird. Das
+    /// Startverzeichnis zählt als Tiefe 1; mit 1 (oder 0) werden nur die
+    /// Dateien des Startverzeichnisses selbst durchsucht. Ohne Angabe wird
+    /// uneingeschränkt rekursiert.
+    #[arg(short = 'd', long, default_value_t = u32::MAX)]
     max_depth: u32,
 
+    /// Der Hash-Algorithmus, mit dem Dateien auf Gleichheit geprüft werden.
+    ///
+    /// `xxh3` ist nicht kryptographisch, aber deutlich schneller als die
+    /// übrigen Algorithmen und für die Duplikatsuche ausreichend. `sha256`
+    /// steht weiterhin zur Verfügung, falls Kollisionsresistenz gefordert
+    /// ist.
+    #[arg(long, value_enum, default_value_t = HashAlgorithm::Xxh3)]
+    hash_algorithm: HashAlgorithm,
+
+    /// Deaktiviert den persistenten Hash-Cache vollständig.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Pfad zur Cache-Datei. Ohne Angabe wird [cache::default_cache_path]
+    /// verwendet.
+    #[arg(long, value_name = "FILE")]
+    cache_path: Option<PathBuf>,
+
+    /// Durchsucht nur Dateien mit einer dieser Endungen, z.B. "jpg,png,mp4".
+    ///
+    /// Ohne Angabe werden Dateien mit allen Endungen berücksichtigt.
+    #[arg(long, value_delimiter = ',', value_name = "EXT,EXT,...")]
+    allowed_extensions: Vec<String>,
+
+    /// Schließt Dateien mit einer dieser Endungen von der Suche aus, z.B.
+    /// "tmp,log".
+    #[arg(long, value_delimiter = ',', value_name = "EXT,EXT,...")]
+    excluded_extensions: Vec<String>,
+
+    /// Verzeichnisse, die von der Suche ausgeschlossen werden, z.B.
+    /// ".git" oder "node_modules". Akzeptiert Glob-Muster.
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Löscht alle bis auf die erste Datei jeder gefundenen Duplikatgruppe.
+    #[arg(long, conflicts_with = "hardlink")]
+    delete: bool,
+
+    /// Ersetzt alle bis auf die erste Datei jeder gefundenen
+    /// Duplikatgruppe durch einen Hardlink auf die erste Datei.
+    #[arg(long, conflicts_with = "delete")]
+    hardlink: bool,
+
+    /// Zeigt nur, was --delete bzw. --hardlink tun würden, ohne etwas zu
+    /// verändern.
+    #[arg(long)]
+    dry_run: bool,
+
     /// Die Verzeichnisse, in denen nach doppelten Dateien gesucht wird.
     ///
     /// Wenn kein Verzeichnis angegeben ist, wird das aktuelle Verzeichnis
@@ -39,6 +99,79 @@ struct Args {
 enum Output {
     Human,
     Machine,
+    Json,
+}
+
+#[derive(Copy, Clone, PartialEq, ValueEnum, Debug, Serialize, Deserialize)]
+enum HashAlgorithm {
+    Xxh3,
+    Crc32,
+    Blake3,
+    Sha256,
+}
+
+// Ein über das gewählte Verfahren hinweg einheitliches Interface, damit
+// same_size_to_partial/partial_to_checksums nicht wissen müssen, welcher
+// konkrete Algorithmus gerade läuft.
+trait DigestHasher {
+    fn update(&mut self, data: &[u8]);
+    fn finish(self: Box<Self>) -> [u8; 32];
+}
+
+impl DigestHasher for Xxh3 {
+    fn update(&mut self, data: &[u8]) {
+        Xxh3::update(self, data)
+    }
+
+    // xxh3 liefert nur 8 Byte; der Rest des Arrays bleibt Null, damit der
+    // Schlüsseltyp [u8; 32] für alle Algorithmen gleich bleiben kann.
+    fn finish(self: Box<Self>) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out[..8].copy_from_slice(&self.digest().to_le_bytes());
+        out
+    }
+}
+
+impl DigestHasher for crc32fast::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        crc32fast::Hasher::update(self, data)
+    }
+
+    // crc32 liefert nur 4 Byte; der Rest des Arrays bleibt Null.
+    fn finish(self: Box<Self>) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out[..4].copy_from_slice(&self.finalize().to_le_bytes());
+        out
+    }
+}
+
+impl DigestHasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> [u8; 32] {
+        *self.finalize().as_bytes()
+    }
+}
+
+impl DigestHasher for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data)
+    }
+
+    fn finish(self: Box<Self>) -> [u8; 32] {
+        self.finalize().into()
+    }
+}
+
+fn new_hasher(algo: HashAlgorithm) -> Box<dyn DigestHasher> {
+    match algo {
+        HashAlgorithm::Xxh3 => Box::new(Xxh3::new()),
+        HashAlgorithm::Crc32 => Box::new(crc32fast::Hasher::new()),
+        HashAlgorithm::Blake3 => Box::new(blake3::Hasher::new()),
+        HashAlgorithm::Sha256 => Box::new(Sha256::new()),
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -60,7 +193,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
         1 => mode1(&args, &args.directories[0])?,
         _ => {
-            todo!();
+            let (reference, rest) = args.directories.split_first().unwrap();
+            mode2(&args, reference, rest)?;
         }
     }
 
@@ -72,67 +206,101 @@ enum Entry {
     Error { path: PathBuf, err: std::io::Error },
 }
 
+// Erlaubt- bzw. Ausschlussliste von Dateiendungen, mit der der Walker
+// gefiltert wird. Ein leere Erlaubt-Liste bedeutet "alle Endungen".
+#[derive(Clone, Default)]
+struct ExtensionFilter {
+    allowed: Option<HashSet<String>>,
+    excluded: HashSet<String>,
+}
+
+impl ExtensionFilter {
+    fn new(allowed: &[String], excluded: &[String]) -> ExtensionFilter {
+        let normalize = |list: &[String]| -> HashSet<String> {
+            list.iter().map(|ext| ext.to_lowercase()).collect()
+        };
+
+        ExtensionFilter {
+            allowed: if allowed.is_empty() {
+                None
+            } else {
+                Some(normalize(allowed))
+            },
+            excluded: normalize(excluded),
+        }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        match &extension {
+            Some(ext) if self.excluded.contains(ext) => false,
+            Some(ext) => self
+                .allowed
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(ext)),
+            None => self.allowed.is_none(),
+        }
+    }
+}
+
 struct Walker {
-    iterator_stack: Vec<ReadDir>,
+    // Jeder Eintrag ist ein noch offenes Verzeichnis zusammen mit seiner
+    // Tiefe relativ zum Startverzeichnis (Startverzeichnis = Tiefe 0).
+    iterator_stack: Vec<(ReadDir, u32)>,
+    filter: ExtensionFilter,
+    max_depth: u32,
+    excludes: Vec<glob::Pattern>,
 }
 
 impl Walker {
-    fn new<P>(path: P) -> Result<Walker, std::io::Error>
+    fn new<P>(
+        path: P,
+        filter: ExtensionFilter,
+        max_depth: u32,
+        excludes: Vec<glob::Pattern>,
+    ) -> Result<Walker, std::io::Error>
     where
         P: AsRef<Path>,
     {
         let mut walker = Walker {
             iterator_stack: Vec::new(),
+            filter,
+            max_depth,
+            excludes,
         };
-        walker.iterator_stack.push(fs::read_dir(path)?);
+        walker.iterator_stack.push((fs::read_dir(path)?, 0));
         Ok(walker)
     }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        let name = path.file_name().and_then(|name| name.to_str());
+
+        self.excludes.iter().any(|pattern| {
+            pattern.matches_path(path) || name.is_some_and(|name| pattern.matches(name))
+        })
+    }
+}
+
+// Wandelt die rohen --exclude Muster in kompilierte Glob-Patterns um.
+// Ungültige Muster werden ignoriert.
+fn compile_excludes(patterns: &[String]) -> Vec<glob::Pattern> {
+    patterns
+        .iter()
+        .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+        .collect()
 }
 
 #[derive(Clone)]
 enum Same {
     SameSize(Vec<PathBuf>),
+    Partial(ChecksumMap),
     Checksums(ChecksumMap),
 }
 
-impl Same {
-    fn print(&self, args: &Args) {
-        match args.output {
-            Output::Human => match self {
-                Same::SameSize(paths) => {
-                    println!("{}", paths[0].display());
-                    for duplicate in &paths[1..] {
-                        println!("    {}", duplicate.display())
-                    }
-                }
-                Same::Checksums(map) => {
-                    for (_, paths) in map {
-                        println!("{}", paths[0].display());
-                        for duplicate in &paths[1..] {
-                            println!("    {}", duplicate.display())
-                        }
-                    }
-                }
-            },
-            Output::Machine => match self {
-                Same::SameSize(paths) => {
-                    for duplicate in &paths[1..] {
-                        println!("{}", duplicate.display())
-                    }
-                }
-
-                Same::Checksums(map) => {
-                    for (_, paths) in map {
-                        for duplicate in &paths[1..] {
-                            println!("{}", duplicate.display())
-                        }
-                    }
-                }
-            },
-        }
-    }
-}
-
 type ChecksumMap = HashMap<[u8; 32], Vec<PathBuf>>;
 
 struct Duplicates(HashMap<u64, Same>);
@@ -153,7 +321,7 @@ impl Duplicates {
                     s.candidates += files - 1;
                     s.bytes += size * (files - 1)
                 }
-                Same::Checksums(map) => {
+                Same::Partial(map) | Same::Checksums(map) => {
                     for (_hash, paths) in map {
                         let files = paths.len() as u64;
                         s.files += files;
@@ -168,7 +336,7 @@ impl Duplicates {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize)]
 struct Summary {
     files: u64,
     candidates: u64,
@@ -214,7 +382,8 @@ impl Iterator for Walker {
             return None;
         }
 
-        let current_iterator = &mut self.iterator_stack[len - 1];
+        let (current_iterator, depth) = &mut self.iterator_stack[len - 1];
+        let depth = *depth;
 
         if let Some(result) = current_iterator.next() {
             match result {
@@ -226,15 +395,22 @@ impl Iterator for Walker {
                     match entry.file_type() {
                         Ok(typ) => {
                             if typ.is_dir() {
+                                if self.is_excluded(&path) || depth + 1 >= self.max_depth {
+                                    return self.next();
+                                }
                                 return match fs::read_dir(&path) {
                                     Ok(neu) => {
-                                        self.iterator_stack.push(neu);
+                                        self.iterator_stack.push((neu, depth + 1));
                                         self.next()
                                     }
                                     Err(err) => Some(Entry::Error { path, err }),
                                 };
                             } else if typ.is_file() {
-                                Some(Entry::File { path, len })
+                                if self.filter.matches(&path) {
+                                    Some(Entry::File { path, len })
+                                } else {
+                                    self.next()
+                                }
                             } else {
                                 self.next()
                             }
@@ -293,7 +469,7 @@ use std::iter;
 
 fn as_path_iterator(item: &Same) -> impl Iterator<Item = &Vec<PathBuf>> + '_ {
    let result =  match item {
-        Same::Checksums(cs) => {
+        Same::Partial(cs) | Same::Checksums(cs) => {
             Box::new(cs.values()) as Box<dyn Iterator<Item = &Vec<PathBuf>>>
             // todo!()
         }
@@ -315,18 +491,23 @@ fn as_path_iterator(item: &Same) -> impl Iterator<Item = &Vec<PathBuf>> + '_ {
     // }
 }
 
-fn print_result(args: &Args, result: &Duplicates) {
+// Generiere eine Liste mit allen Gruppen von Dateien mit gleicher Größe
+// bzw. gleicher Prüfsumme, sortiert nach dem ersten Pfad jeder Gruppe.
+// Das Kriterium (Größe/Prüfsumme) geht bei dieser Operation verloren.
+fn sorted_groups(result: &Duplicates) -> Vec<&Vec<PathBuf>> {
+    let mut groups: Vec<&Vec<PathBuf>> = result.0.values().flat_map(as_path_iterator).collect();
 
-    // Generiere eine Liste mit allen Gruppen von Dateien mit
-    // gleicher Größe bzw. gleicher Prüfsumme. Das Kriterium
-    // geht bei dieser Operation verloren.
-    let mut x: Vec<&Vec<PathBuf>> = result.0.values().flat_map(as_path_iterator).collect();
+    groups.sort_by(|a, b| (**a)[0].cmp(&(**b)[0]));
 
-    x.sort_by(|a, b| {
-        (**a)[0].cmp(&(**b)[0])
-    });
+    groups
+}
 
-    for candidates in x {
+fn print_result(args: &Args, result: &Duplicates) {
+    if args.output == Output::Json {
+        return print_json(args, result);
+    }
+
+    for candidates in sorted_groups(result) {
 
         match args.output {
             Output::Human => {
@@ -342,31 +523,204 @@ fn print_result(args: &Args, result: &Duplicates) {
                     }
                 }
             }
+            Output::Json => unreachable!(),
         }
     }
 
-    // for (key, same) in &result.0 {
-    //     same.print(args)
-    // }
-
     let summary = result.summarize();
     summary.print(args);
 }
 
-fn same_size_to_checksums((size, same): (u64, Same)) -> (u64, Same) {
+// Eine Gruppe von Dateien im JSON-Ausgabeformat: Größe, der verwendete
+// Hash-Algorithmus und der Hash selbst (sofern für diese Gruppe bereits
+// gehasht wurde, statt nur nach Größe gruppiert) sowie die Pfade.
+#[derive(Serialize)]
+struct JsonGroup<'a> {
+    size: u64,
+    algorithm: Option<HashAlgorithm>,
+    hash: Option<String>,
+    paths: &'a Vec<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct JsonResult<'a> {
+    groups: Vec<JsonGroup<'a>>,
+    summary: Summary,
+}
+
+// Wie die Machine-Ausgabe enthält auch die JSON-Ausgabe nur tatsächliche
+// Duplikatgruppen (mehr als eine Datei); eindeutige Dateien werden
+// ausgeschlossen. Das vermeidet zugleich, dass eine eindeutige Gruppe
+// ihren Partial-Hash fälschlich als vollständigen Hash ausgibt, da
+// solche Gruppen nie vollständig gehasht werden.
+fn json_groups(result: &Duplicates, algo: HashAlgorithm) -> Vec<JsonGroup<'_>> {
+    let mut groups: Vec<JsonGroup> = Vec::new();
+
+    for (size, same) in &result.0 {
+        match same {
+            Same::SameSize(paths) if paths.len() > 1 => groups.push(JsonGroup {
+                size: *size,
+                algorithm: None,
+                hash: None,
+                paths,
+            }),
+            Same::SameSize(_) => {}
+            Same::Partial(map) | Same::Checksums(map) => {
+                for (hash, paths) in map {
+                    if paths.len() > 1 {
+                        groups.push(JsonGroup {
+                            size: *size,
+                            algorithm: Some(algo),
+                            hash: Some(to_hex(hash)),
+                            paths,
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    groups.sort_by(|a, b| a.paths[0].cmp(&b.paths[0]));
+    groups
+}
+
+fn to_hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn print_json(args: &Args, result: &Duplicates) {
+    let output = JsonResult {
+        groups: json_groups(result, args.hash_algorithm),
+        summary: result.summarize(),
+    };
+
+    // TODO: was soll passieren, wenn die Serialisierung fehlschlägt?
+    if let Ok(json) = serde_json::to_string_pretty(&output) {
+        println!("{json}");
+    }
+}
+
+// Löschen/Hardlink-Aktionen auf den gefundenen Duplikaten. Die erste
+// Datei jeder Gruppe (gleiche Sortierreihenfolge wie in print_result)
+// bleibt erhalten, alle weiteren gelten als verzichtbare Duplikate.
+fn apply_actions(args: &Args, result: &Duplicates) {
+    if !args.delete && !args.hardlink {
+        return;
+    }
+
+    for candidates in sorted_groups(result) {
+        let Some((kept, duplicates)) = candidates.split_first() else {
+            continue;
+        };
+
+        for duplicate in duplicates {
+            if args.delete {
+                delete_duplicate(args, duplicate);
+            } else if args.hardlink {
+                hardlink_duplicate(args, kept, duplicate);
+            }
+        }
+    }
+}
+
+fn delete_duplicate(args: &Args, duplicate: &Path) {
+    if args.dry_run {
+        println!("{} {}", "löschen:".red(), duplicate.display());
+        return;
+    }
+
+    // TODO: was soll passieren, wenn die Datei nicht gelöscht werden kann?
+    if let Err(err) = fs::remove_file(duplicate) {
+        eprintln!("{}: {}", duplicate.display(), err);
+    }
+}
+
+fn hardlink_duplicate(args: &Args, kept: &Path, duplicate: &Path) {
+    if args.dry_run {
+        println!(
+            "{} {} -> {}",
+            "hardlinken:".green(),
+            duplicate.display(),
+            kept.display()
+        );
+        return;
+    }
+
+    // Erst auf einen temporären Namen verlinken und dann atomar
+    // umbenennen, damit die Originaldatei bei einem Fehler erhalten
+    // bleibt.
+    let temp = temp_path_for(duplicate);
+
+    // TODO: was soll passieren, wenn Link/Umbenennen fehlschlägt?
+    if let Err(err) = fs::hard_link(kept, &temp) {
+        eprintln!("{}: {}", duplicate.display(), err);
+        return;
+    }
+    if let Err(err) = fs::rename(&temp, duplicate) {
+        eprintln!("{}: {}", duplicate.display(), err);
+        let _ = fs::remove_file(&temp);
+    }
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".findup-tmp");
+    PathBuf::from(name)
+}
+
+// Größe des Blocks, der für den Partial-Hash gelesen wird. Dateien, die
+// kleiner als ein Block sind, werden dadurch beim Partial-Pass bereits
+// vollständig gehasht und brauchen im Full-Pass nicht erneut gelesen zu
+// werden.
+const BLOCK_SIZE: u64 = 4096;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum HashMode {
+    Partial,
+    Full,
+}
+
+fn hash_file(path: &Path, mode: HashMode, algo: HashAlgorithm) -> Option<[u8; 32]> {
+    use std::io::Read;
+
+    let mut hasher = new_hasher(algo);
+    match mode {
+        HashMode::Partial => {
+            let file = fs::File::open(path).ok()?;
+            let mut block = Vec::new();
+            file.take(BLOCK_SIZE).read_to_end(&mut block).ok()?;
+            hasher.update(&block);
+        }
+        HashMode::Full => {
+            let content = fs::read(path).ok()?;
+            hasher.update(&content);
+        }
+    }
+    Some(hasher.finish())
+}
+
+// 2. Pass: Partial-Hash. Dateien gleicher Größe werden anhand der
+// Prüfsumme ihres ersten Blocks gruppiert. Dateien, deren Partial-Hash
+// innerhalb ihrer Größen-Gruppe eindeutig ist, sind damit als
+// Nicht-Duplikate erkannt, ohne dass sie jemals vollständig gelesen
+// wurden.
+fn same_size_to_partial((size, same): (u64, Same), algo: HashAlgorithm) -> (u64, Same) {
     if let Same::SameSize(paths) = &same {
         if paths.len() > 1 {
-            let mut map = ChecksumMap::new();
+            let hashes: Vec<(PathBuf, Option<[u8; 32]>)> = paths
+                .par_iter()
+                .map(|path| (path.to_owned(), hash_file(path, HashMode::Partial, algo)))
+                .collect();
 
-            for path in paths {
+            let mut map = ChecksumMap::new();
+            for (path, hash) in hashes {
                 // TODO: was soll passieren, wenn die Datei nicht gelesen werden kann?
-                if let Ok(content) = fs::read(path) {
-                    let hash: [u8; 32] = Sha256::digest(content).into();
-                    let entry = map.entry(hash).or_insert_with(|| Vec::new());
-                    entry.push(path.to_owned())
+                if let Some(hash) = hash {
+                    let entry = map.entry(hash).or_default();
+                    entry.push(path)
                 }
             }
-            (size, Same::Checksums(map))
+            (size, Same::Partial(map))
         } else {
             (size, same.clone())
         }
@@ -375,17 +729,337 @@ fn same_size_to_checksums((size, same): (u64, Same)) -> (u64, Same) {
     }
 }
 
+// 3. Pass: Full-Hash. Nur Gruppen, die nach dem Partial-Hash noch aus
+// mehr als einer Datei bestehen, werden vollständig gelesen und erneut
+// gehasht. Eindeutige Partial-Hash-Gruppen werden unverändert übernommen.
+// Ist eine Datei nicht größer als BLOCK_SIZE, deckt ihr Partial-Hash
+// bereits die gesamte Datei ab; ein erneutes Lesen und Hashen im
+// Full-Pass entfällt dann auch innerhalb mehrdeutiger Gruppen.
+fn partial_to_checksums(
+    (size, same): (u64, Same),
+    algo: HashAlgorithm,
+    cache: &Mutex<Cache>,
+    use_cache: bool,
+) -> (u64, Same) {
+    if let Same::Partial(partial) = &same {
+        let mut map = ChecksumMap::new();
+
+        for (partial_hash, paths) in partial {
+            if paths.len() > 1 && size > BLOCK_SIZE {
+                let hashes: Vec<(PathBuf, Option<[u8; 32]>)> = paths
+                    .par_iter()
+                    .map(|path| {
+                        (
+                            path.to_owned(),
+                            hash_file_cached(path, size, algo, cache, use_cache),
+                        )
+                    })
+                    .collect();
+
+                for (path, hash) in hashes {
+                    // TODO: was soll passieren, wenn die Datei nicht gelesen werden kann?
+                    if let Some(hash) = hash {
+                        let entry = map.entry(hash).or_default();
+                        entry.push(path)
+                    }
+                }
+            } else {
+                map.insert(*partial_hash, paths.clone());
+            }
+        }
+        (size, Same::Checksums(map))
+    } else {
+        (size, same.clone())
+    }
+}
+
+// Full-Hash mit Cache-Anbindung: Vor dem Lesen der Datei wird geprüft,
+// ob für Pfad, Größe, Änderungszeitpunkt und Algorithmus bereits ein
+// Eintrag im Cache vorliegt. Nur bei einem Cache-Miss wird tatsächlich
+// gehasht, und das Ergebnis wird anschließend zurückgeschrieben. Der
+// Cache steckt in einem Mutex, weil mehrere Dateien parallel gehasht
+// werden.
+fn hash_file_cached(
+    path: &Path,
+    size: u64,
+    algo: HashAlgorithm,
+    cache: &Mutex<Cache>,
+    use_cache: bool,
+) -> Option<[u8; 32]> {
+    let mtime = fs::metadata(path).ok().and_then(|md| md.modified().ok());
+
+    if use_cache {
+        if let Some(mtime) = mtime {
+            if let Some(hash) = cache.lock().unwrap().get(path, size, mtime, algo) {
+                return Some(hash);
+            }
+        }
+    }
+
+    let hash = hash_file(path, HashMode::Full, algo)?;
+
+    if use_cache {
+        if let Some(mtime) = mtime {
+            cache
+                .lock()
+                .unwrap()
+                .insert(path.to_owned(), size, mtime, algo, hash);
+        }
+    }
+
+    Some(hash)
+}
+
 // Nur ein Verzeichnis nach Duplikaten durchsuchen
 fn mode1<P>(args: &Args, path: P) -> Result<(), std::io::Error>
 where
     P: AsRef<Path>,
 {
+    let use_cache = !args.no_cache;
+    let cache_path = args
+        .cache_path
+        .clone()
+        .unwrap_or_else(cache::default_cache_path);
+    let cache = Mutex::new(if use_cache {
+        Cache::load(&cache_path)
+    } else {
+        Cache::default()
+    });
+
+    let filter = ExtensionFilter::new(&args.allowed_extensions, &args.excluded_extensions);
+    let excludes = compile_excludes(&args.exclude);
+
     // 1. Pass: File size
-    let walker = Walker::new(path)?;
+    let walker = Walker::new(path, filter, args.max_depth, excludes)?;
     let pass1 = walker.fold(Duplicates::new(), group_by_len);
-    let pass2: Duplicates = pass1.0.into_iter().map(same_size_to_checksums).collect();
+    // 2. Pass: Partial-Hash. Die Größen-Buckets sind voneinander
+    // unabhängig und werden daher parallel verarbeitet.
+    let pass2: Duplicates = Duplicates(
+        pass1
+            .0
+            .into_par_iter()
+            .map(|item| same_size_to_partial(item, args.hash_algorithm))
+            .collect(),
+    );
+    // 3. Pass: Full-Hash
+    let pass3: Duplicates = Duplicates(
+        pass2
+            .0
+            .into_par_iter()
+            .map(|item| partial_to_checksums(item, args.hash_algorithm, &cache, use_cache))
+            .collect(),
+    );
+
+    print_result(args, &pass3);
+    apply_actions(args, &pass3);
+
+    if use_cache {
+        // TODO: was soll passieren, wenn der Cache nicht geschrieben werden kann?
+        let _ = cache.into_inner().unwrap().save(&cache_path);
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ReferenceMatch {
+    size: u64,
+    reference: PathBuf,
+    duplicate: PathBuf,
+}
+
+// Mehrere Verzeichnisse: das erste Verzeichnis ist die Referenzmenge.
+// Ausgegeben werden nur Dateien aus den nachfolgenden Verzeichnissen, die
+// ein Duplikat einer Datei aus der Referenzmenge sind.
+fn mode2(args: &Args, reference: &Path, others: &[PathBuf]) -> Result<(), std::io::Error> {
+    let use_cache = !args.no_cache;
+    let cache_path = args
+        .cache_path
+        .clone()
+        .unwrap_or_else(cache::default_cache_path);
+    let cache = Mutex::new(if use_cache {
+        Cache::load(&cache_path)
+    } else {
+        Cache::default()
+    });
+
+    let filter = ExtensionFilter::new(&args.allowed_extensions, &args.excluded_extensions);
+    let excludes = compile_excludes(&args.exclude);
+
+    // Referenz-Index: Größe -> Hash -> Pfad. Jede Referenzdatei wird
+    // dabei genau einmal gehasht, statt für jeden Kandidaten aus den
+    // übrigen Verzeichnissen erneut gelesen zu werden.
+    let reference_walker = Walker::new(reference, filter.clone(), args.max_depth, excludes.clone())?;
+    let mut reference_index: HashMap<u64, HashMap<[u8; 32], PathBuf>> = HashMap::new();
+    for entry in reference_walker {
+        let Entry::File { path, len } = entry else {
+            continue;
+        };
+
+        let Some(hash) = hash_file_cached(&path, len, args.hash_algorithm, &cache, use_cache)
+        else {
+            continue;
+        };
+
+        reference_index
+            .entry(len)
+            .or_default()
+            .entry(hash)
+            .or_insert(path);
+    }
+
+    let mut matches: Vec<ReferenceMatch> = Vec::new();
+
+    for dir in others {
+        let walker = Walker::new(dir, filter.clone(), args.max_depth, excludes.clone())?;
+        for entry in walker {
+            let Entry::File { path, len } = entry else {
+                continue;
+            };
 
-    print_result(args, &pass2);
+            let Some(by_hash) = reference_index.get(&len) else {
+                continue;
+            };
+
+            let Some(hash) = hash_file_cached(&path, len, args.hash_algorithm, &cache, use_cache)
+            else {
+                continue;
+            };
+
+            let Some(reference_path) = by_hash.get(&hash) else {
+                continue;
+            };
+
+            if args.output == Output::Json {
+                matches.push(ReferenceMatch {
+                    size: len,
+                    reference: reference_path.clone(),
+                    duplicate: path.clone(),
+                });
+            } else {
+                print_reference_match(args, reference_path, &path);
+            }
+
+            // Die Referenzdatei bleibt stets erhalten; nur das Duplikat
+            // aus den übrigen Verzeichnissen wird gelöscht bzw. durch
+            // einen Hardlink auf die Referenzdatei ersetzt.
+            if args.delete {
+                delete_duplicate(args, &path);
+            } else if args.hardlink {
+                hardlink_duplicate(args, reference_path, &path);
+            }
+        }
+    }
+
+    if args.output == Output::Json {
+        // TODO: was soll passieren, wenn die Serialisierung fehlschlägt?
+        if let Ok(json) = serde_json::to_string_pretty(&matches) {
+            println!("{json}");
+        }
+    }
+
+    if use_cache {
+        // TODO: was soll passieren, wenn der Cache nicht geschrieben werden kann?
+        let _ = cache.into_inner().unwrap().save(&cache_path);
+    }
 
     Ok(())
 }
+
+fn print_reference_match(args: &Args, reference: &Path, duplicate: &Path) {
+    match args.output {
+        Output::Human => {
+            println!("{}", reference.display());
+            println!("    {}", duplicate.display());
+        }
+        Output::Machine => {
+            println!("{}", duplicate.display());
+        }
+        Output::Json => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Eindeutiges Testverzeichnis unter dem System-Temp-Pfad, damit
+    // parallel laufende Tests sich nicht in die Quere kommen.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "findup-test-{name}-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn walk(dir: &Path, max_depth: u32, filter: ExtensionFilter) -> Vec<PathBuf> {
+        Walker::new(dir, filter, max_depth, Vec::new())
+            .unwrap()
+            .filter_map(|entry| match entry {
+                Entry::File { path, .. } => Some(path),
+                Entry::Error { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_max_depth_descends_into_subdirectories() {
+        let dir = temp_dir("default-depth");
+        fs::write(dir.join("top.txt"), b"top").unwrap();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("nested.txt"), b"nested").unwrap();
+
+        let found = walk(&dir, u32::MAX, ExtensionFilter::default());
+
+        assert!(found.iter().any(|p| p.ends_with("nested.txt")));
+        assert!(found.iter().any(|p| p.ends_with("top.txt")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn max_depth_one_does_not_descend() {
+        let dir = temp_dir("shallow-depth");
+        fs::write(dir.join("top.txt"), b"top").unwrap();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("nested.txt"), b"nested").unwrap();
+
+        let found = walk(&dir, 1, ExtensionFilter::default());
+
+        assert!(found.iter().any(|p| p.ends_with("top.txt")));
+        assert!(!found.iter().any(|p| p.ends_with("nested.txt")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn excluded_extension_wins_over_allowed() {
+        let filter = ExtensionFilter::new(
+            &["txt".to_string()],
+            &["txt".to_string()],
+        );
+
+        assert!(!filter.matches(Path::new("a.txt")));
+    }
+
+    #[test]
+    fn allowed_extensions_restrict_the_scan() {
+        let filter = ExtensionFilter::new(&["jpg".to_string()], &[]);
+
+        assert!(filter.matches(Path::new("a.jpg")));
+        assert!(!filter.matches(Path::new("a.png")));
+    }
+
+    #[test]
+    fn empty_allowed_list_means_all_extensions() {
+        let filter = ExtensionFilter::new(&[], &["tmp".to_string()]);
+
+        assert!(filter.matches(Path::new("a.png")));
+        assert!(!filter.matches(Path::new("a.tmp")));
+    }
+}